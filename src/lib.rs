@@ -1,35 +1,129 @@
 use std::fmt::{self, Display};
 
+// 入力中の位置をbyteオフセットだけでなく行・桁でも表せるようにしたもの。
+// line/colは1始まりで、colは文字数で数える(バイト数ではない)。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub byte: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+fn span_at(origin: &str, byte: usize) -> Span {
+    let mut line = 1;
+    let mut col = 1;
+    for (index, ch) in origin.char_indices() {
+        if index == byte {
+            break;
+        }
+        if ch == '\n' {
+            line += 1;
+            col = 1;
+        } else {
+            col += 1;
+        }
+    }
+    Span { byte, line, col }
+}
+
 #[derive(Debug)]
 pub enum ParseError {
-    HasNoClosing(usize),
+    HasNoClosing(Span),
+    MismatchedClosing {
+        open_at: Span,
+        close_at: Span,
+        expected: char,
+        found: char,
+    },
+    UnexpectedClosing(Span),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::HasNoClosing(span) => {
+                write!(f, "not closed at line {}, column {}", span.line, span.col)
+            }
+            ParseError::MismatchedClosing {
+                open_at,
+                close_at,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "mismatched closing bracket at line {}, column {}: expected '{}' (opened at line {}, column {}) but found '{}'",
+                    close_at.line, close_at.col, expected, open_at.line, open_at.col, found
+                )
+            }
+            ParseError::UnexpectedClosing(span) => {
+                write!(
+                    f,
+                    "unexpected closing bracket at line {}, column {}",
+                    span.line, span.col
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ASTKind {
+    Text(String),                            // normal text
+    Parenthesis(Box<AST>),                   // ()
+    Curly(Box<AST>),                         // {}
+    Square(Box<AST>),                        // []
+    Angle(Box<AST>),                         // <>
+    Quoted { delim: char, inner: Box<AST> }, // '...' or "..."
+    Tokens(Vec<AST>), // normal text and {parenthesis (maybe nest) or not} or not
 }
 
+// origin中でそのノードが占めるbyte範囲(開始, 終了)を持つASTノード。
+// 括弧・引用符ノードの範囲は開き文字から閉じ文字の直後までを含む。
 #[derive(Debug)]
-pub enum AST {
-    Text(String),          // normal text
-    Parenthesis(Box<AST>), // ()
-    Curly(Box<AST>),       // {}
-    Square(Box<AST>),      // []
-    Tokens(Vec<AST>),      // normal text and {parenthesis (maybe nest) or not} or not
+pub struct AST {
+    kind: ASTKind,
+    span: (usize, usize),
+}
+
+impl AST {
+    pub fn kind(&self) -> &ASTKind {
+        &self.kind
+    }
+
+    pub fn span(&self) -> (usize, usize) {
+        self.span
+    }
 }
 
 impl Display for AST {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.kind, f)
+    }
+}
+
+impl Display for ASTKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            AST::Text(inner) => {
+            ASTKind::Text(inner) => {
                 write!(f, "AST::Text({})", inner)
             }
-            AST::Parenthesis(inner) => {
+            ASTKind::Parenthesis(inner) => {
                 write!(f, "AST::Parenthesis({})", inner)
             }
-            AST::Curly(inner) => {
+            ASTKind::Curly(inner) => {
                 write!(f, "AST::Curly({})", inner)
             }
-            AST::Square(inner) => {
+            ASTKind::Square(inner) => {
                 write!(f, "AST::Square({})", inner)
             }
-            AST::Tokens(tokens) => {
+            ASTKind::Angle(inner) => {
+                write!(f, "AST::Angle({})", inner)
+            }
+            ASTKind::Quoted { delim, inner } => {
+                write!(f, "AST::Quoted({}, {})", delim, inner)
+            }
+            ASTKind::Tokens(tokens) => {
                 write!(f, "AST::Tokens([ ")?;
                 for token in tokens {
                     write!(f, "{}, ", token)?;
@@ -40,176 +134,352 @@ impl Display for AST {
     }
 }
 
-pub fn parse(origin: &str) -> Result<AST, ParseError> {
-    if origin.len() == 0 {
-        return Ok(AST::Text(String::from("")));
-    }
-    let mut tokens: Vec<AST> = Vec::new();
-    let mut last_string_start: usize = 0;
-    let mut index: usize = 0;
-    loop {
-        let ch = match origin.chars().nth(index) {
-            Some(c) => c,
-            None => {
-                break;
+// フレームを開いたのが括弧か引用符か(ルートフレームならNone)。どちらも開き位置を持つ。
+enum OpenKind {
+    Bracket(Bracket, usize),
+    Quote(char, usize),
+}
+
+// 各フレームはそのフレームを開いたもの、ここまでに積んだ子トークン、
+// 現在のテキスト区間の開始byte位置、そしてフレームの内容が始まったbyte位置を持つ
+struct Frame {
+    open: Option<OpenKind>,
+    tokens: Vec<AST>,
+    text_start: usize,
+    content_start: usize,
+}
+
+impl Frame {
+    fn new(open: Option<OpenKind>, content_start: usize) -> Self {
+        Frame {
+            open,
+            tokens: Vec::new(),
+            text_start: content_start,
+            content_start,
+        }
+    }
+
+    fn push_text(&mut self, origin: &str, start: usize, end: usize) {
+        if start != end {
+            self.tokens.push(AST {
+                kind: ASTKind::Text(origin[start..end].to_string()),
+                span: (start, end),
+            });
+        }
+    }
+
+    // content_endはこのフレームの内容が終わるbyte位置(閉じ文字そのものは含まない)
+    fn wrap(mut self, content_end: usize) -> AST {
+        let content_span = (self.content_start, content_end);
+        let inner = if self.tokens.is_empty() {
+            AST {
+                kind: ASTKind::Text(String::new()),
+                span: content_span,
+            }
+        } else if self.tokens.len() == 1 {
+            self.tokens.pop().unwrap()
+        } else {
+            AST {
+                kind: ASTKind::Tokens(self.tokens),
+                span: content_span,
             }
         };
-        match ch {
-            '(' | '{' | '[' => {
-                if last_string_start != index {
-                    tokens.push(AST::Text(origin[last_string_start..index].to_string()));
-                }
-                let b: Bracket;
-                if ch == '(' {
-                    b = Bracket::Paren;
-                } else if ch == '{' {
-                    b = Bracket::Curly;
-                } else {
-                    b = Bracket::Square;
-                }
 
-                // NOTE: サーチとパースで2回走査するので多分遅い
-                let end_index = match search_end_bracket(&origin[index..], &b) {
-                    Some(i) => i,
-                    None => {
-                        return Err(ParseError::HasNoClosing(index));
-                    }
+        match self.open {
+            Some(OpenKind::Bracket(bracket, open_at)) => {
+                let span = (open_at, content_end + bracket.closing_char().len_utf8());
+                let kind = match bracket {
+                    Bracket::Paren => ASTKind::Parenthesis(Box::new(inner)),
+                    Bracket::Curly => ASTKind::Curly(Box::new(inner)),
+                    Bracket::Square => ASTKind::Square(Box::new(inner)),
+                    Bracket::Angle => ASTKind::Angle(Box::new(inner)),
                 };
+                AST { kind, span }
+            }
+            Some(OpenKind::Quote(delim, open_at)) => {
+                let span = (open_at, content_end + delim.len_utf8());
+                AST {
+                    kind: ASTKind::Quoted {
+                        delim,
+                        inner: Box::new(inner),
+                    },
+                    span,
+                }
+            }
+            None => inner,
+        }
+    }
+}
 
-                let parsed_inner = match parse(&origin[(index + 1)..(index + end_index)]) {
-                    Ok(ast) => ast,
-                    Err(err) => {
-                        return Err(err);
-                    }
-                };
+#[derive(Clone, Copy)]
+enum Bracket {
+    Paren,
+    Curly,
+    Square,
+    Angle,
+}
 
-                match b {
-                    Bracket::Paren => tokens.push(AST::Parenthesis(Box::new(parsed_inner))),
-                    Bracket::Curly => tokens.push(AST::Curly(Box::new(parsed_inner))),
-                    Bracket::Square => tokens.push(AST::Square(Box::new(parsed_inner))),
-                }
+impl Bracket {
+    fn opening_char(&self) -> char {
+        match self {
+            Bracket::Paren => '(',
+            Bracket::Curly => '{',
+            Bracket::Square => '[',
+            Bracket::Angle => '<',
+        }
+    }
 
-                last_string_start = index + end_index + 1;
-                index = index + end_index + 1;
-            }
-            _ => {
-                index += 1;
-            }
+    fn closing_char(&self) -> char {
+        match self {
+            Bracket::Paren => ')',
+            Bracket::Curly => '}',
+            Bracket::Square => ']',
+            Bracket::Angle => '>',
+        }
+    }
+}
+
+// どの括弧・引用符を有効にするかを表す。Default::default()は従来通り()/{}/[]のみを認識する。
+pub struct BracketConfig {
+    brackets: Vec<Bracket>,
+    quotes: Vec<char>,
+}
+
+impl BracketConfig {
+    pub fn new() -> Self {
+        BracketConfig {
+            brackets: vec![Bracket::Paren, Bracket::Curly, Bracket::Square],
+            quotes: Vec::new(),
         }
     }
 
-    if last_string_start < origin.len() - 1 || origin.len() == 1 {
-        tokens.push(AST::Text(origin[last_string_start..].to_string()));
+    pub fn with_angle(mut self) -> Self {
+        self.brackets.push(Bracket::Angle);
+        self
+    }
+
+    pub fn with_quote(mut self, delim: char) -> Self {
+        self.quotes.push(delim);
+        self
+    }
+
+    fn bracket_for_open(&self, ch: char) -> Option<Bracket> {
+        self.brackets
+            .iter()
+            .find(|b| b.opening_char() == ch)
+            .copied()
     }
 
-    if tokens.len() == 1 {
-        return Ok(tokens.pop().unwrap());
+    fn bracket_for_close(&self, ch: char) -> Option<Bracket> {
+        self.brackets
+            .iter()
+            .find(|b| b.closing_char() == ch)
+            .copied()
     }
 
-    Ok(AST::Tokens(tokens))
+    fn is_quote(&self, ch: char) -> bool {
+        self.quotes.contains(&ch)
+    }
 }
 
-enum Bracket {
-    Paren,
-    Curly,
-    Square,
+impl Default for BracketConfig {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-// 渡された文字列から閉じ括弧の場所(文字列のindex)を検出する
-// ↓の場合6を返す
-// origin -> (12345).....
-// 適切な閉じ括弧がない場合はNoneを返す
-fn search_end_bracket(origin: &str, bracket: &Bracket) -> Option<usize> {
-    let targets: (char, char);
-    let mut target_count = 0;
-    match bracket {
-        Bracket::Paren => {
-            targets = ('(', ')');
-        }
-        Bracket::Curly => {
-            targets = ('{', '}');
-        }
-        Bracket::Square => {
-            targets = ('[', ']');
-        }
-    }
-    for (index, ch) in origin.chars().enumerate() {
-        match ch {
-            '(' | '{' | '[' => {
-                if targets.0 == ch {
-                    target_count = target_count + 1;
-                }
+pub fn parse(origin: &str) -> Result<AST, ParseError> {
+    parse_with(origin, &BracketConfig::default())
+}
+
+pub fn parse_with(origin: &str, config: &BracketConfig) -> Result<AST, ParseError> {
+    if origin.is_empty() {
+        return Ok(AST {
+            kind: ASTKind::Text(String::from("")),
+            span: (0, 0),
+        });
+    }
+
+    let mut stack: Vec<Frame> = vec![Frame::new(None, 0)];
+
+    for (index, ch) in origin.char_indices() {
+        // 引用符の中にいる間は、対応する終端引用符だけを探し、他はすべて文字として扱う
+        // (括弧のネストも引用符が閉じるまでは普通のテキストになる)
+        if let Some(OpenKind::Quote(delim, _)) = stack.last().unwrap().open {
+            if ch == delim {
+                close_frame(&mut stack, origin, index, ch);
             }
-            ')' | '}' | ']' => {
-                if targets.1 == ch {
-                    target_count = target_count - 1;
-                    if target_count == 0 {
-                        return Some(index);
-                    }
+            continue;
+        }
+
+        if let Some(bracket) = config.bracket_for_open(ch) {
+            let top = stack.last_mut().unwrap();
+            let text_start = top.text_start;
+            top.push_text(origin, text_start, index);
+            stack.push(Frame::new(
+                Some(OpenKind::Bracket(bracket, index)),
+                index + ch.len_utf8(),
+            ));
+            continue;
+        }
+
+        if config.is_quote(ch) {
+            let top = stack.last_mut().unwrap();
+            let text_start = top.text_start;
+            top.push_text(origin, text_start, index);
+            stack.push(Frame::new(
+                Some(OpenKind::Quote(ch, index)),
+                index + ch.len_utf8(),
+            ));
+            continue;
+        }
+
+        if config.bracket_for_close(ch).is_some() {
+            let (open_bracket, open_at) = match &stack.last().unwrap().open {
+                Some(OpenKind::Bracket(b, at)) => (*b, *at),
+                Some(OpenKind::Quote(..)) => unreachable!("handled above"),
+                None => {
+                    return Err(ParseError::UnexpectedClosing(span_at(origin, index)));
                 }
+            };
+            if open_bracket.closing_char() != ch {
+                return Err(ParseError::MismatchedClosing {
+                    open_at: span_at(origin, open_at),
+                    close_at: span_at(origin, index),
+                    expected: open_bracket.closing_char(),
+                    found: ch,
+                });
             }
-            _ => {}
+            close_frame(&mut stack, origin, index, ch);
         }
     }
-    //最後まで見つからなかったのでNoneを返す
-    None
+
+    if stack.len() != 1 {
+        // 最も外側の閉じていない開き括弧・引用符を報告する
+        let open_at = match stack[1].open.as_ref().unwrap() {
+            OpenKind::Bracket(_, at) => *at,
+            OpenKind::Quote(_, at) => *at,
+        };
+        return Err(ParseError::HasNoClosing(span_at(origin, open_at)));
+    }
+
+    let mut root = stack.pop().unwrap();
+    let text_start = root.text_start;
+    root.push_text(origin, text_start, origin.len());
+
+    Ok(root.wrap(origin.len()))
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+// 現在のフレームのテキストを確定させ、popして出来上がったノードを親フレームに積む
+fn close_frame(stack: &mut Vec<Frame>, origin: &str, index: usize, ch: char) {
+    let end = index + ch.len_utf8();
+    let mut top = stack.pop().unwrap();
+    let text_start = top.text_start;
+    top.push_text(origin, text_start, index);
+    let node = top.wrap(index);
+    let parent = stack.last_mut().unwrap();
+    parent.tokens.push(node);
+    parent.text_start = end;
+}
 
-    #[test]
-    fn search_end_bracket_paranthesis() {
-        let origin = "(123456)texttext";
-        assert_eq!(7, search_end_bracket(origin, &Bracket::Paren).unwrap());
+// origin中の括弧を1回のスタック走査で対応付ける。configで有効になっている括弧の種類だけを見る
+// (parse_withと同じ設定を渡せば、<...>のような有効化された区切りも正しく対応付けられる)。
+// 開き括弧でpushし、対応する閉じ括弧でpopして(open_index, close_index)を記録する。
+// 種類が合わない閉じ括弧やEOFまでに閉じなかった開き括弧は単に無視する(ベストエフォート)。
+// max_scanを指定すると、そこまでの文字数を見ても見つからなければ打ち切る。
+fn collect_bracket_pairs(
+    origin: &str,
+    config: &BracketConfig,
+    max_scan: Option<usize>,
+) -> Vec<(usize, usize)> {
+    let mut stack: Vec<(usize, Bracket)> = Vec::new();
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
 
-        let double = "(123456(89))texttext";
-        assert_eq!(11, search_end_bracket(double, &Bracket::Paren).unwrap());
+    for (scanned, (index, ch)) in origin.char_indices().enumerate() {
+        if let Some(limit) = max_scan {
+            if scanned >= limit {
+                break;
+            }
+        }
+        if let Some(bracket) = config.bracket_for_open(ch) {
+            stack.push((index, bracket));
+            continue;
+        }
+        if config.bracket_for_close(ch).is_some() {
+            if let Some((_, bracket)) = stack.last() {
+                if bracket.closing_char() == ch {
+                    let (open_index, _) = stack.pop().unwrap();
+                    pairs.push((open_index, index));
+                }
+            }
+        }
     }
-    #[test]
-    fn search_end_bracket_curly() {
-        let origin = "{123456}texttext";
-        assert_eq!(7, search_end_bracket(origin, &Bracket::Curly).unwrap());
 
-        let double = "{123456{89}}texttext";
-        assert_eq!(11, search_end_bracket(double, &Bracket::Curly).unwrap());
-    }
-    #[test]
-    fn search_end_bracket_square() {
-        let origin = "[123456]texttext";
-        assert_eq!(7, search_end_bracket(origin, &Bracket::Square).unwrap());
+    pairs
+}
 
-        let double = "[123456[89]]texttext";
-        assert_eq!(11, search_end_bracket(double, &Bracket::Square).unwrap());
-    }
-    #[test]
-    fn search_end_bracket_none() {
-        let origin = "(123456texttext";
-        assert_eq!(None, search_end_bracket(origin, &Bracket::Square));
+// offsetが開き括弧の上にあれば対応する閉じ括弧のindexを、閉じ括弧の上にあれば対応する開き括弧の
+// indexを返す。エディタのカーソル位置からの括弧ジャンプ用途を想定している。
+// configで有効にした括弧の種類だけが対象(parse_withに渡したものと同じconfigを渡すこと)。
+pub fn find_matching_bracket(
+    origin: &str,
+    offset: usize,
+    config: &BracketConfig,
+    max_scan: Option<usize>,
+) -> Option<usize> {
+    for (open, close) in collect_bracket_pairs(origin, config, max_scan) {
+        if open == offset {
+            return Some(close);
+        }
+        if close == offset {
+            return Some(open);
+        }
     }
+    None
+}
+
+// offsetを厳密に内側に含む、最も内側の(開き括弧, 閉じ括弧)のペアを返す。
+// configで有効にした括弧の種類だけが対象(parse_withに渡したものと同じconfigを渡すこと)。
+pub fn find_enclosing_pair(
+    origin: &str,
+    offset: usize,
+    config: &BracketConfig,
+    max_scan: Option<usize>,
+) -> Option<(usize, usize)> {
+    collect_bracket_pairs(origin, config, max_scan)
+        .into_iter()
+        .filter(|(open, close)| *open < offset && offset < *close)
+        .min_by_key(|(open, close)| close - open)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
 
     #[test]
     fn parse_single_text() {
         let ast = parse("t").unwrap();
-        if let AST::Text(str) = ast {
+        if let ASTKind::Text(str) = ast.kind() {
             assert_eq!(str, "t");
         } else {
             assert!(false);
         }
+        assert_eq!(ast.span(), (0, 1));
     }
 
     #[test]
     fn parse_single_bracket() {
         let ast = parse("text(a)").unwrap();
-        if let AST::Tokens(tokens) = ast {
+        if let ASTKind::Tokens(tokens) = ast.kind() {
             assert_eq!(tokens.len(), 2);
-            assert!(matches!(tokens[0], AST::Text(_)));
-            if let AST::Parenthesis(a) = &tokens[1] {
-                assert!(matches!(**a, AST::Text(_)))
+            assert!(matches!(tokens[0].kind(), ASTKind::Text(_)));
+            if let ASTKind::Parenthesis(a) = tokens[1].kind() {
+                assert!(matches!(a.kind(), ASTKind::Text(_)));
+                assert_eq!(a.span(), (5, 6));
             } else {
                 assert!(false);
             }
+            assert_eq!(tokens[1].span(), (4, 7));
         } else {
             assert!(false);
         };
@@ -218,15 +488,15 @@ mod test {
     #[test]
     fn parse_single_curly() {
         let ast = parse("text{aaa}test").unwrap();
-        if let AST::Tokens(tokens) = ast {
+        if let ASTKind::Tokens(tokens) = ast.kind() {
             assert_eq!(tokens.len(), 3);
-            assert!(matches!(tokens[0], AST::Text(_)));
-            if let AST::Curly(a) = &tokens[1] {
-                assert!(matches!(**a, AST::Text(_)))
+            assert!(matches!(tokens[0].kind(), ASTKind::Text(_)));
+            if let ASTKind::Curly(a) = tokens[1].kind() {
+                assert!(matches!(a.kind(), ASTKind::Text(_)))
             } else {
                 assert!(false)
             }
-            assert!(matches!(tokens[2], AST::Text(_)));
+            assert!(matches!(tokens[2].kind(), ASTKind::Text(_)));
         } else {
             assert!(false);
         };
@@ -235,24 +505,254 @@ mod test {
     #[test]
     fn parse_single_square() {
         let ast = parse("text[aaa]test").unwrap();
-        if let AST::Tokens(tokens) = ast {
+        if let ASTKind::Tokens(tokens) = ast.kind() {
             assert_eq!(tokens.len(), 3);
-            assert!(matches!(tokens[0], AST::Text(_)));
-            if let AST::Square(a) = &tokens[1] {
-                assert!(matches!(**a, AST::Text(_)))
+            assert!(matches!(tokens[0].kind(), ASTKind::Text(_)));
+            if let ASTKind::Square(a) = tokens[1].kind() {
+                assert!(matches!(a.kind(), ASTKind::Text(_)))
             } else {
                 assert!(false)
             }
-            assert!(matches!(tokens[2], AST::Text(_)));
+            assert!(matches!(tokens[2].kind(), ASTKind::Text(_)));
         } else {
             assert!(false);
         };
     }
 
+    #[test]
+    fn parse_empty_bracket_contents_are_empty_text() {
+        let ast = parse("()").unwrap();
+        if let ASTKind::Parenthesis(inner) = ast.kind() {
+            if let ASTKind::Text(text) = inner.kind() {
+                assert_eq!(text, "");
+            } else {
+                assert!(false);
+            }
+        } else {
+            assert!(false);
+        }
+
+        let ast = parse("{}").unwrap();
+        if let ASTKind::Curly(inner) = ast.kind() {
+            assert!(matches!(inner.kind(), ASTKind::Text(text) if text.is_empty()));
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn parse_empty_angle_and_quote_contents_are_empty_text() {
+        let config = BracketConfig::new().with_angle().with_quote('\'');
+
+        let ast = parse_with("<>", &config).unwrap();
+        if let ASTKind::Angle(inner) = ast.kind() {
+            assert!(matches!(inner.kind(), ASTKind::Text(text) if text.is_empty()));
+        } else {
+            assert!(false);
+        }
+
+        let ast = parse_with("''", &config).unwrap();
+        if let ASTKind::Quoted { inner, .. } = ast.kind() {
+            assert!(matches!(inner.kind(), ASTKind::Text(text) if text.is_empty()));
+        } else {
+            assert!(false);
+        }
+    }
+
     #[test]
     fn parse_not_closing_bracket() {
+        match parse("text(aaatest") {
+            Err(ParseError::HasNoClosing(span)) => {
+                assert_eq!(span.byte, 4);
+                assert_eq!(span.line, 1);
+                assert_eq!(span.col, 5);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn parse_mismatched_closing() {
         match parse("text(aaa]test") {
-            Err(e) => assert!(matches!(e, ParseError::HasNoClosing(4))),
+            Err(ParseError::MismatchedClosing {
+                open_at,
+                close_at,
+                expected,
+                found,
+            }) => {
+                assert_eq!(open_at.byte, 4);
+                assert_eq!(close_at.byte, 8);
+                assert_eq!(expected, ')');
+                assert_eq!(found, ']');
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn parse_mismatched_closing_simple() {
+        match parse("(a]b)") {
+            Err(ParseError::MismatchedClosing {
+                expected, found, ..
+            }) => {
+                assert_eq!(expected, ')');
+                assert_eq!(found, ']');
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn parse_unexpected_closing() {
+        match parse("text)test") {
+            Err(ParseError::UnexpectedClosing(span)) => {
+                assert_eq!(span.byte, 4);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn parse_error_line_and_column_multiline() {
+        match parse("line1\nline2(unterminated") {
+            Err(ParseError::HasNoClosing(span)) => {
+                assert_eq!(span.line, 2);
+                assert_eq!(span.col, 6);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn find_matching_bracket_on_open_and_close() {
+        let origin = "a(bc)d";
+        let config = BracketConfig::default();
+        assert_eq!(find_matching_bracket(origin, 1, &config, None), Some(4));
+        assert_eq!(find_matching_bracket(origin, 4, &config, None), Some(1));
+    }
+
+    #[test]
+    fn find_matching_bracket_nested() {
+        let origin = "(a{b[c]d}e)";
+        let config = BracketConfig::default();
+        assert_eq!(find_matching_bracket(origin, 0, &config, None), Some(10));
+        assert_eq!(find_matching_bracket(origin, 2, &config, None), Some(8));
+        assert_eq!(find_matching_bracket(origin, 4, &config, None), Some(6));
+    }
+
+    #[test]
+    fn find_matching_bracket_not_on_a_bracket() {
+        let origin = "a(bc)d";
+        let config = BracketConfig::default();
+        assert_eq!(find_matching_bracket(origin, 0, &config, None), None);
+    }
+
+    #[test]
+    fn find_matching_bracket_respects_max_scan() {
+        let origin = "a(bc)d";
+        let config = BracketConfig::default();
+        assert_eq!(find_matching_bracket(origin, 1, &config, Some(2)), None);
+    }
+
+    #[test]
+    fn find_matching_bracket_with_angle_config() {
+        let origin = "a<bc>d";
+        let config = BracketConfig::new().with_angle();
+        assert_eq!(find_matching_bracket(origin, 1, &config, None), Some(4));
+        assert_eq!(find_matching_bracket(origin, 4, &config, None), Some(1));
+    }
+
+    #[test]
+    fn find_matching_bracket_ignores_disabled_angle_bracket() {
+        let origin = "a<bc>d";
+        let config = BracketConfig::default();
+        assert_eq!(find_matching_bracket(origin, 1, &config, None), None);
+    }
+
+    #[test]
+    fn find_enclosing_pair_innermost() {
+        let origin = "(a{b[c]d}e)";
+        let config = BracketConfig::default();
+        assert_eq!(find_enclosing_pair(origin, 5, &config, None), Some((4, 6)));
+        assert_eq!(find_enclosing_pair(origin, 3, &config, None), Some((2, 8)));
+        assert_eq!(find_enclosing_pair(origin, 9, &config, None), Some((0, 10)));
+    }
+
+    #[test]
+    fn find_enclosing_pair_none_outside_brackets() {
+        let origin = "a(bc)d";
+        let config = BracketConfig::default();
+        assert_eq!(find_enclosing_pair(origin, 0, &config, None), None);
+    }
+
+    #[test]
+    fn parse_with_angle_bracket() {
+        let config = BracketConfig::new().with_angle();
+        let ast = parse_with("text<a>test", &config).unwrap();
+        if let ASTKind::Tokens(tokens) = ast.kind() {
+            assert_eq!(tokens.len(), 3);
+            if let ASTKind::Angle(a) = tokens[1].kind() {
+                assert!(matches!(a.kind(), ASTKind::Text(_)))
+            } else {
+                assert!(false)
+            }
+        } else {
+            assert!(false);
+        };
+    }
+
+    #[test]
+    fn parse_without_angle_bracket_is_plain_text() {
+        let ast = parse("text<a>test").unwrap();
+        if let ASTKind::Text(text) = ast.kind() {
+            assert_eq!(text, "text<a>test");
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn parse_with_quote() {
+        let config = BracketConfig::new().with_quote('\'').with_quote('"');
+        let ast = parse_with("text'aaa'test", &config).unwrap();
+        if let ASTKind::Tokens(tokens) = ast.kind() {
+            assert_eq!(tokens.len(), 3);
+            if let ASTKind::Quoted { delim, inner } = tokens[1].kind() {
+                assert_eq!(*delim, '\'');
+                assert!(matches!(inner.kind(), ASTKind::Text(_)))
+            } else {
+                assert!(false)
+            }
+        } else {
+            assert!(false);
+        };
+    }
+
+    #[test]
+    fn parse_quote_treats_brackets_inside_as_literal_text() {
+        let config = BracketConfig::new().with_quote('"');
+        let ast = parse_with("a\"b(c\"d", &config).unwrap();
+        if let ASTKind::Tokens(tokens) = ast.kind() {
+            if let ASTKind::Quoted { delim, inner } = tokens[1].kind() {
+                assert_eq!(*delim, '"');
+                if let ASTKind::Text(text) = inner.kind() {
+                    assert_eq!(text, "b(c");
+                } else {
+                    assert!(false);
+                }
+            } else {
+                assert!(false);
+            }
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn parse_unclosed_quote_is_has_no_closing() {
+        let config = BracketConfig::new().with_quote('"');
+        match parse_with("text\"unterminated", &config) {
+            Err(ParseError::HasNoClosing(span)) => assert_eq!(span.byte, 4),
             _ => assert!(false),
         }
     }
@@ -264,4 +764,26 @@ mod test {
         // TODO:: 真面目に書く
         assert!(true)
     }
+
+    #[test]
+    fn span_covers_nested_brackets() {
+        let ast = parse("a(b{c}d)e").unwrap();
+        assert_eq!(ast.span(), (0, 9));
+        if let ASTKind::Tokens(tokens) = ast.kind() {
+            // tokens[1] is the Parenthesis node spanning "(b{c}d)"
+            assert_eq!(tokens[1].span(), (1, 8));
+            if let ASTKind::Parenthesis(inner) = tokens[1].kind() {
+                if let ASTKind::Tokens(inner_tokens) = inner.kind() {
+                    // inner_tokens[1] is the Curly node spanning "{c}"
+                    assert_eq!(inner_tokens[1].span(), (3, 6));
+                } else {
+                    assert!(false);
+                }
+            } else {
+                assert!(false);
+            }
+        } else {
+            assert!(false);
+        }
+    }
 }
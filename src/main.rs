@@ -12,11 +12,9 @@ fn main() {
 
     match bracket_parser::parse(&args[1]) {
         Ok(ast) => println!("parsed: {}", ast),
-        Err(e) => match e {
-            bracket_parser::ParseError::HasNoClosing(at) => {
-                eprintln!("not close at: {}", at);
-                process::exit(1)
-            }
-        },
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1)
+        }
     }
 }